@@ -1,65 +1,123 @@
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::PathBuf;
 use std::sync::Mutex;
-use tauri::{AppHandle, Wry};
+use tauri::{AppHandle, Manager, Wry};
 use tauri_plugin_store::StoreExt;
 
+use crate::secret::{self, SecretBackend};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiKeyStore {
     api_key: Option<String>,
     #[serde(skip)]
     app_handle: Option<AppHandle<Wry>>,
+    #[serde(skip)]
+    backend: Option<SecretBackend>,
 }
 
 const STORE_FILE: &str = "kiassist_config.json";
+// Legacy plaintext field, migrated out of the JSON store on first run.
 const API_KEY_FIELD: &str = "gemini_api_key";
+// Non-sensitive marker recording whether a key is stashed in the secure backend.
+const API_KEY_PRESENT_FIELD: &str = "gemini_api_key_present";
 
 impl ApiKeyStore {
     pub fn new() -> Self {
         // Try to load from environment variable first
         let api_key = env::var("GEMINI_API_KEY").ok();
-        Self { 
+        Self {
             api_key,
             app_handle: None,
+            backend: None,
         }
     }
-    
+
     pub fn set_app_handle(&mut self, handle: AppHandle<Wry>) {
         self.app_handle = Some(handle.clone());
-        
-        // Try to load from persistent store if environment variable not set
-        if self.api_key.is_none() {
-            if let Ok(store) = handle.store(STORE_FILE) {
-                if let Some(key) = store.get(API_KEY_FIELD) {
-                    if let Some(key_str) = key.as_str() {
-                        self.api_key = Some(key_str.to_string());
+
+        let dir = secret_dir(&handle);
+
+        // Migrate any existing plaintext key out of the JSON store into the
+        // secure backend. Only scrub the plaintext once the key is safely in a
+        // backend; if that fails, leave the plaintext untouched rather than
+        // destroying the user's only copy.
+        if let Ok(store) = handle.store(STORE_FILE) {
+            if let Some(plaintext) = store
+                .get(API_KEY_FIELD)
+                .and_then(|v| v.as_str().map(str::to_string))
+            {
+                match secret::store_secret(&dir, &plaintext) {
+                    Ok(backend) => {
+                        self.backend = Some(backend);
+                        if self.api_key.is_none() {
+                            self.api_key = Some(plaintext);
+                        }
+                        store.delete(API_KEY_FIELD);
+                        store.set(API_KEY_PRESENT_FIELD, serde_json::Value::Bool(true));
+                        let _ = store.save();
+                    }
+                    Err(e) => {
+                        // Keep using the plaintext key this run; it stays in the
+                        // store so migration can be retried on the next launch.
+                        eprintln!("API key migration failed, keeping plaintext: {}", e);
+                        if self.api_key.is_none() {
+                            self.api_key = Some(plaintext);
+                        }
                     }
                 }
             }
         }
+
+        // Load from the secure backend if we don't already have a key.
+        if self.api_key.is_none() {
+            if let Some((key, backend)) = secret::load_secret(&dir) {
+                self.api_key = Some(key);
+                self.backend = Some(backend);
+            }
+        }
     }
-    
+
     pub fn get_api_key(&self) -> Option<String> {
         self.api_key.clone()
     }
-    
+
     pub fn set_api_key(&mut self, key: String) -> Result<(), String> {
         self.api_key = Some(key.clone());
-        
-        // Persist to store
+
+        // Persist to the secure backend, leaving only a flag in the JSON store.
         if let Some(handle) = &self.app_handle {
+            let dir = secret_dir(handle);
+            let backend = secret::store_secret(&dir, &key)?;
+            self.backend = Some(backend);
+
             if let Ok(store) = handle.store(STORE_FILE) {
-                store.set(API_KEY_FIELD, serde_json::Value::String(key));
-                store.save().map_err(|e| format!("Failed to save API key: {}", e))?;
+                store.set(API_KEY_PRESENT_FIELD, serde_json::Value::Bool(true));
+                store
+                    .save()
+                    .map_err(|e| format!("Failed to save API key flag: {}", e))?;
             }
         }
-        
+
         Ok(())
     }
-    
+
     pub fn has_api_key(&self) -> bool {
         self.api_key.is_some()
     }
+
+    /// Which secure backend is protecting the stored key.
+    pub fn secret_backend(&self) -> SecretBackend {
+        self.backend.unwrap_or_else(secret::active_backend)
+    }
+}
+
+/// Directory used for the file-encrypted fallback, alongside the app config.
+fn secret_dir(handle: &AppHandle<Wry>) -> PathBuf {
+    handle
+        .path()
+        .app_config_dir()
+        .unwrap_or_else(|_| env::temp_dir())
 }
 
 pub struct ApiKeyState(pub Mutex<ApiKeyStore>);
@@ -0,0 +1,126 @@
+// Per-session conversation history for the Gemini client.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::gemini::ChatTurn;
+
+/// Identifier for a single chat session (one conversation thread in the UI).
+pub type SessionId = String;
+
+/// Default number of turns retained per session.
+const DEFAULT_MAX_TURNS: usize = 40;
+/// Default token ceiling per session (approximate; see [`estimate_tokens`]).
+const DEFAULT_MAX_TOKENS: usize = 500_000;
+/// Rough characters-per-token ratio used to estimate a turn's token cost
+/// without pulling in a tokenizer dependency.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Configurable budget bounding how much history is sent to the model.
+///
+/// Long sessions are trimmed from the front until they fit within BOTH the
+/// turn and (estimated) token limits, so a few very long turns can't blow past
+/// the context window the way a turn-only cap would.
+#[derive(Debug, Clone, Copy)]
+pub struct HistoryBudget {
+    pub max_turns: usize,
+    pub max_tokens: usize,
+}
+
+impl Default for HistoryBudget {
+    fn default() -> Self {
+        Self {
+            max_turns: DEFAULT_MAX_TURNS,
+            max_tokens: DEFAULT_MAX_TOKENS,
+        }
+    }
+}
+
+/// Approximate the token cost of a turn from its character length.
+fn estimate_tokens(turn: &ChatTurn) -> usize {
+    turn.text.len() / CHARS_PER_TOKEN + 1
+}
+
+/// Trim `history` in place from the front until it fits within `budget`.
+pub(crate) fn trim_history(history: &mut Vec<ChatTurn>, budget: &HistoryBudget) {
+    if history.len() > budget.max_turns {
+        let overflow = history.len() - budget.max_turns;
+        history.drain(..overflow);
+    }
+
+    let mut total: usize = history.iter().map(estimate_tokens).sum();
+    while total > budget.max_tokens && history.len() > 1 {
+        let dropped = estimate_tokens(&history.remove(0));
+        total = total.saturating_sub(dropped);
+    }
+}
+
+pub struct ChatState {
+    pub sessions: Mutex<HashMap<SessionId, Vec<ChatTurn>>>,
+    pub budget: HistoryBudget,
+}
+
+impl ChatState {
+    pub fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            budget: HistoryBudget::default(),
+        }
+    }
+}
+
+impl Default for ChatState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turns(n: usize) -> Vec<ChatTurn> {
+        (0..n).map(|i| ChatTurn::user(format!("turn {}", i))).collect()
+    }
+
+    #[test]
+    fn test_trim_history_respects_turn_budget() {
+        let budget = HistoryBudget {
+            max_turns: 3,
+            max_tokens: usize::MAX,
+        };
+        let mut history = turns(5);
+        trim_history(&mut history, &budget);
+        assert_eq!(history.len(), 3);
+        // Oldest turns are dropped first.
+        assert_eq!(history[0].text, "turn 2");
+    }
+
+    #[test]
+    fn test_trim_history_respects_token_budget() {
+        let budget = HistoryBudget {
+            max_turns: usize::MAX,
+            max_tokens: 5,
+        };
+        let mut history = vec![
+            ChatTurn::user("a".repeat(40)),
+            ChatTurn::user("b".repeat(40)),
+            ChatTurn::user("short"),
+        ];
+        trim_history(&mut history, &budget);
+        // Always keep at least the most recent turn.
+        assert_eq!(history.last().unwrap().text, "short");
+        assert!(history.len() < 3);
+    }
+
+    #[test]
+    fn test_trim_history_keeps_last_turn() {
+        let budget = HistoryBudget {
+            max_turns: usize::MAX,
+            max_tokens: 1,
+        };
+        let mut history = vec![ChatTurn::user("a".repeat(1000))];
+        trim_history(&mut history, &budget);
+        assert_eq!(history.len(), 1);
+    }
+}
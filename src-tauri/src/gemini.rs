@@ -1,20 +1,104 @@
 use anyhow::Result;
+use futures_util::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Wry};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct GeminiRequest {
     contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<Tool>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Content {
+    #[serde(default)]
+    role: String,
     parts: Vec<Part>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A single turn in a conversation, as stored per session and sent to Gemini.
+///
+/// `role` is `"user"` for prompts and `"model"` for the assistant's replies,
+/// matching the values the Gemini `contents` array expects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub role: String,
+    pub text: String,
+}
+
+impl ChatTurn {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: "user".to_string(),
+            text: text.into(),
+        }
+    }
+
+    pub fn model(text: impl Into<String>) -> Self {
+        Self {
+            role: "model".to_string(),
+            text: text.into(),
+        }
+    }
+
+    fn to_content(&self) -> Content {
+        Content {
+            role: self.role.clone(),
+            parts: vec![Part::text(self.text.clone())],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct Part {
-    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function_call: Option<FunctionCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function_response: Option<FunctionResponse>,
+}
+
+impl Part {
+    fn text(text: impl Into<String>) -> Self {
+        Part {
+            text: Some(text.into()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A `functionCall` part returned by the model, requesting a tool invocation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FunctionCall {
+    name: String,
+    #[serde(default)]
+    args: serde_json::Value,
+}
+
+/// A `functionResponse` part we feed back after executing a tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FunctionResponse {
+    name: String,
+    response: serde_json::Value,
+}
+
+/// A `tools` entry carrying the function declarations exposed to the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Tool {
+    #[serde(rename = "functionDeclarations")]
+    function_declarations: Vec<FunctionDeclaration>,
+}
+
+/// A single Gemini `functionDeclaration`: name, description, JSON-schema args.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionDeclaration {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -27,35 +111,37 @@ struct Candidate {
     content: Content,
 }
 
+/// Map a short model name to the full Gemini model ID.
+///
+/// Using the stable Gemini models that are available.
+fn model_id_for(model: &str) -> &'static str {
+    match model {
+        "1.5-flash" => "gemini-1.5-flash",      // Fast, cost-effective (1M token context)
+        "1.5-pro" => "gemini-1.5-pro",          // Complex reasoning (2M token context)
+        "1.5-flash-8b" => "gemini-1.5-flash-8b", // High volume, low latency (1M token context)
+        _ => "gemini-1.5-flash",                 // Default to flash
+    }
+}
+
 pub async fn send_message_to_gemini(
     api_key: &str,
     model: &str,
-    message: &str,
+    history: &[ChatTurn],
 ) -> Result<String> {
     let client = Client::new();
-    
-    // Map model name to full Gemini model ID
-    // Using the stable Gemini models that are available
-    let model_id = match model {
-        "1.5-flash" => "gemini-1.5-flash",      // Fast, cost-effective (1M token context)
-        "1.5-pro" => "gemini-1.5-pro",          // Complex reasoning (2M token context)
-        "1.5-flash-8b" => "gemini-1.5-flash-8b", // High volume, low latency (1M token context)
-        _ => "gemini-1.5-flash",                 // Default to flash
-    };
-    
+
+    let model_id = model_id_for(model);
+
     let url = format!(
         "https://generativelanguage.googleapis.com/v1/models/{}:generateContent?key={}",
         model_id, api_key
     );
-    
+
     let request_body = GeminiRequest {
-        contents: vec![Content {
-            parts: vec![Part {
-                text: message.to_string(),
-            }],
-        }],
+        contents: history.iter().map(ChatTurn::to_content).collect(),
+        tools: None,
     };
-    
+
     let response = client
         .post(&url)
         .json(&request_body)
@@ -70,10 +156,202 @@ pub async fn send_message_to_gemini(
     let gemini_response: GeminiResponse = response.json().await?;
     
     if let Some(candidate) = gemini_response.candidates.first() {
-        if let Some(part) = candidate.content.parts.first() {
-            return Ok(part.text.clone());
+        if let Some(text) = candidate.content.parts.iter().find_map(|p| p.text.clone()) {
+            return Ok(text);
         }
     }
-    
+
     Err(anyhow::anyhow!("No response from Gemini API"))
 }
+
+/// Stream a Gemini completion to the frontend as Tauri events.
+///
+/// Hits the `:streamGenerateContent?alt=sse` endpoint and forwards each parsed
+/// delta to the webview as `gemini://chunk/{stream_id}` carrying the
+/// incremental `part.text`. This function emits no terminal event itself; the
+/// `send_message_stream` command emits `gemini://done/{stream_id}` when it
+/// returns `Ok` and `gemini://error/{stream_id}` when it returns `Err`.
+pub async fn stream_message_to_gemini(
+    app: &AppHandle<Wry>,
+    api_key: &str,
+    model: &str,
+    message: &str,
+    stream_id: &str,
+) -> Result<()> {
+    let client = Client::new();
+
+    let model_id = model_id_for(model);
+
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1/models/{}:streamGenerateContent?alt=sse&key={}",
+        model_id, api_key
+    );
+
+    let request_body = GeminiRequest {
+        contents: vec![ChatTurn::user(message).to_content()],
+        tools: None,
+    };
+
+    let response = client.post(&url).json(&request_body).send().await?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await?;
+        return Err(anyhow::anyhow!("Gemini API error: {}", error_text));
+    }
+
+    // The server-sent event stream delivers one `data:` line per partial
+    // response. We buffer raw bytes and only decode at newline boundaries, so a
+    // multibyte codepoint split across two network frames is never corrupted.
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        let bytes = chunk?;
+        buffer.extend_from_slice(&bytes);
+
+        while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+            let line_bytes: Vec<u8> = buffer.drain(..=newline).collect();
+            // A complete line is valid UTF-8; decode it in isolation.
+            let line = String::from_utf8_lossy(&line_bytes);
+            let line = line.trim();
+
+            let Some(data) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let data = data.trim();
+            if data.is_empty() || data == "[DONE]" {
+                continue;
+            }
+
+            // A single malformed SSE line (e.g. a keep-alive) must not abort the
+            // whole stream; skip it and keep reading.
+            let partial: GeminiResponse = match serde_json::from_str(data) {
+                Ok(partial) => partial,
+                Err(_) => continue,
+            };
+            if let Some(candidate) = partial.candidates.first() {
+                if let Some(text) = candidate.content.parts.iter().find_map(|p| p.text.as_deref())
+                {
+                    app.emit(&format!("gemini://chunk/{}", stream_id), text)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Upper bound on tool-call rounds before we give up, so a model that keeps
+/// calling functions without ever returning text can't loop forever.
+const MAX_TOOL_ROUNDS: usize = 8;
+
+/// Run a Gemini completion with the KiCad tool registry attached.
+///
+/// Declares the registry's `functionDeclarations`, then loops: each time the
+/// model emits `functionCall` parts we dispatch them against `socket_path`,
+/// feed the `functionResponse` values back into the `contents` array, and
+/// continue until the model returns plain text. Write operations only run when
+/// `allow_writes` is set (the UI's confirmation gate); otherwise the tool
+/// reports the block back to the model as its function response.
+pub async fn send_message_with_tools(
+    api_key: &str,
+    model: &str,
+    history: &[ChatTurn],
+    registry: &crate::kicad_tools::ToolRegistry,
+    socket_path: &str,
+    allow_writes: bool,
+) -> Result<String> {
+    let client = Client::new();
+    let model_id = model_id_for(model);
+    // Function calling (the `tools` field and `functionCall`/`functionResponse`
+    // parts) is only available on the `v1beta` endpoint; `v1` rejects it.
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+        model_id, api_key
+    );
+
+    let mut contents: Vec<Content> = history.iter().map(ChatTurn::to_content).collect();
+    let tools = Some(vec![Tool {
+        function_declarations: registry.declarations(),
+    }]);
+
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let request_body = GeminiRequest {
+            contents: contents.clone(),
+            tools: tools.clone(),
+        };
+
+        let response = client.post(&url).json(&request_body).send().await?;
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            return Err(anyhow::anyhow!("Gemini API error: {}", error_text));
+        }
+
+        let gemini_response: GeminiResponse = response.json().await?;
+        let candidate = gemini_response
+            .candidates
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("No response from Gemini API"))?;
+        let parts = candidate.content.parts;
+
+        let calls: Vec<FunctionCall> =
+            parts.iter().filter_map(|p| p.function_call.clone()).collect();
+
+        // No tool calls means the model is done; return its text.
+        if calls.is_empty() {
+            let text: String = parts
+                .iter()
+                .filter_map(|p| p.text.clone())
+                .collect::<Vec<_>>()
+                .join("");
+            if text.is_empty() {
+                return Err(anyhow::anyhow!("No response from Gemini API"));
+            }
+            return Ok(text);
+        }
+
+        // Echo the model's function-call turn back into the history.
+        contents.push(Content {
+            role: "model".to_string(),
+            parts,
+        });
+
+        // Execute each requested call and collect the responses. The KiCad
+        // connection is opened and dropped here so it never spans an await.
+        let response_parts = {
+            let kicad = crate::kicad_ipc::connect(socket_path)
+                .map_err(|e| anyhow::anyhow!("KiCad connection error: {}", e))?;
+            calls
+                .into_iter()
+                .map(|call| {
+                    let response = match registry
+                        .dispatch(&kicad, &call.name, &call.args, allow_writes)
+                    {
+                        Ok(value) => serde_json::json!({ "result": value }),
+                        Err(err) => serde_json::json!({ "error": err }),
+                    };
+                    Part {
+                        function_response: Some(FunctionResponse {
+                            name: call.name,
+                            response,
+                        }),
+                        ..Default::default()
+                    }
+                })
+                .collect()
+        };
+
+        // Function responses are carried back in a `user`-role turn; the
+        // endpoint's role enum only accepts `user`/`model`.
+        contents.push(Content {
+            role: "user".to_string(),
+            parts: response_parts,
+        });
+    }
+
+    Err(anyhow::anyhow!(
+        "Gemini tool loop exceeded {} rounds without a final answer",
+        MAX_TOOL_ROUNDS
+    ))
+}
@@ -0,0 +1,186 @@
+// Middleware that guards the Tauri invoke surface.
+//
+// Commands are exposed to anything that can reach the IPC bridge, including
+// injected iframes or untrusted webview content. This policy rejects any
+// invocation that does not originate from the main window, and additionally
+// validates the origin of secret-bearing commands against an allowlist before
+// they can touch the API key or spend quota.
+
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime};
+
+/// Label of the window permitted to invoke commands.
+const MAIN_WINDOW_LABEL: &str = "main";
+
+/// Commands that touch the API key or spend the user's API quota; these get
+/// the extra origin check on top of the main-window requirement. Every
+/// quota-spending command MUST be listed here so it can't bypass the guard.
+const SECRET_COMMANDS: &[&str] = &[
+    "set_api_key",
+    "get_api_key",
+    "secret_backend",
+    "send_message",
+    "send_message_stream",
+    "append_and_send",
+    "send_message_with_tools",
+];
+
+/// Error returned for any invocation that fails the guard, kept distinct so
+/// abuse is observable rather than silently served.
+pub const UNAUTHORIZED: &str = "unauthorized invoke origin";
+
+/// Configurable allowlist of webview origins trusted to call secret commands.
+#[derive(Debug, Clone)]
+pub struct TrustedOrigins {
+    main_label: String,
+    origins: Vec<String>,
+}
+
+impl TrustedOrigins {
+    /// Build a policy from an explicit set of trusted origins.
+    pub fn new(main_label: impl Into<String>, origins: Vec<String>) -> Self {
+        Self {
+            main_label: main_label.into(),
+            origins,
+        }
+    }
+
+    fn is_main(&self, label: &str) -> bool {
+        label == self.main_label
+    }
+
+    fn is_secret(command: &str) -> bool {
+        SECRET_COMMANDS.contains(&command)
+    }
+
+    fn is_trusted_origin(&self, origin: &str) -> bool {
+        self.origins.iter().any(|o| o == origin)
+    }
+
+    /// Pure authorization decision for an invocation.
+    ///
+    /// Rejects calls from a non-main window, and secret-bearing commands whose
+    /// `origin` is absent or not allowlisted. `origin` is ignored for
+    /// non-secret commands.
+    pub fn authorize(
+        &self,
+        label: &str,
+        command: &str,
+        origin: Option<&str>,
+    ) -> Result<(), String> {
+        if !self.is_main(label) {
+            return Err(UNAUTHORIZED.to_string());
+        }
+        if Self::is_secret(command) {
+            match origin {
+                Some(origin) if self.is_trusted_origin(origin) => {}
+                _ => return Err(UNAUTHORIZED.to_string()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for TrustedOrigins {
+    fn default() -> Self {
+        // The origins a Tauri webview serves the app from in dev and release.
+        Self::new(
+            MAIN_WINDOW_LABEL,
+            vec![
+                "tauri://localhost".to_string(),
+                "https://tauri.localhost".to_string(),
+                "http://localhost:1420".to_string(),
+            ],
+        )
+    }
+}
+
+/// Validate an incoming invocation against `policy`.
+///
+/// Returns [`UNAUTHORIZED`] if the invoking webview is not the main one, or if
+/// a secret-bearing command arrives while that webview's URL origin is not
+/// allowlisted. Both checks look at the webview as a whole (its label and its
+/// top-level URL), not at the individual frame inside it that made the call.
+pub fn check_invoke<R: Runtime>(policy: &TrustedOrigins, invoke: &Invoke<R>) -> Result<(), String> {
+    let webview = invoke.message.webview();
+    let origin = webview.url().ok().map(|url| {
+        let origin = url.origin();
+        if origin.is_tuple() {
+            origin.ascii_serialization()
+        } else {
+            // Custom schemes such as `tauri://` have an opaque origin that
+            // serializes as "null"; use scheme://host[:port] so they can be
+            // matched against the allowlist.
+            let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+            format!("{}://{}{}", url.scheme(), url.host_str().unwrap_or(""), port)
+        }
+    });
+    policy.authorize(
+        webview.label(),
+        invoke.message.command(),
+        origin.as_deref(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> TrustedOrigins {
+        TrustedOrigins::new("main", vec!["tauri://localhost".to_string()])
+    }
+
+    #[test]
+    fn test_rejects_non_main_window() {
+        let p = policy();
+        assert_eq!(
+            p.authorize("iframe", "echo_message", None),
+            Err(UNAUTHORIZED.to_string())
+        );
+    }
+
+    #[test]
+    fn test_allows_non_secret_from_main() {
+        let p = policy();
+        assert!(p.authorize("main", "echo_message", None).is_ok());
+    }
+
+    #[test]
+    fn test_secret_requires_trusted_origin() {
+        let p = policy();
+        // Trusted origin passes.
+        assert!(p
+            .authorize("main", "send_message", Some("tauri://localhost"))
+            .is_ok());
+        // Untrusted origin is rejected.
+        assert_eq!(
+            p.authorize("main", "send_message", Some("https://evil.example")),
+            Err(UNAUTHORIZED.to_string())
+        );
+        // Missing origin is rejected.
+        assert_eq!(
+            p.authorize("main", "send_message", None),
+            Err(UNAUTHORIZED.to_string())
+        );
+    }
+
+    #[test]
+    fn test_all_quota_commands_are_guarded() {
+        let p = policy();
+        for command in [
+            "set_api_key",
+            "get_api_key",
+            "secret_backend",
+            "send_message",
+            "send_message_stream",
+            "append_and_send",
+            "send_message_with_tools",
+        ] {
+            assert_eq!(
+                p.authorize("main", command, Some("https://evil.example")),
+                Err(UNAUTHORIZED.to_string()),
+                "command {command} should require a trusted origin"
+            );
+        }
+    }
+}
@@ -4,9 +4,15 @@
 
 use kicad::{DocumentType, KiCad, KiCadConnectionConfig, KiCadError};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Wry};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KiCadInstance {
@@ -61,16 +67,20 @@ fn discover_socket_files() -> Vec<PathBuf> {
     sockets
 }
 
-/// Try to connect to a KiCad instance and retrieve its information
-fn probe_kicad_instance(socket_path: &str) -> Result<KiCadInstance, KiCadError> {
+/// Open an IPC connection to the KiCad instance at `socket_path`.
+pub(crate) fn connect(socket_path: &str) -> Result<KiCad, KiCadError> {
     let config = KiCadConnectionConfig {
         socket_path: socket_path.to_string(),
-        client_name: String::from("newkiassist-probe"),
+        client_name: String::from("newkiassist"),
         ..Default::default()
     };
+    KiCad::new(config)
+}
+
+/// Try to connect to a KiCad instance and retrieve its information
+fn probe_kicad_instance(socket_path: &str) -> Result<KiCadInstance, KiCadError> {
+    let kicad = connect(socket_path)?;
 
-    let kicad = KiCad::new(config)?;
-    
     // Get version
     let version = kicad.get_version()?;
     let version_str = version.to_string();
@@ -127,6 +137,119 @@ pub fn detect_kicad_instances() -> Vec<KiCadInstance> {
     instances
 }
 
+/// How often the watcher rescans the socket directory.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Consecutive failed probes before a live instance is declared disconnected.
+///
+/// Debounces transient failures so a KiCad startup race (socket present but
+/// the IPC server not yet accepting) doesn't flap the list.
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// Continuous discovery subsystem that watches the IPC socket directory and
+/// emits connect/disconnect events as KiCad windows come and go.
+///
+/// Modeled on a device watcher: a background poll task maintains a live map of
+/// probed instances and reconciles it against the sockets currently on disk.
+pub struct KiCadWatcher {
+    running: Arc<AtomicBool>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl KiCadWatcher {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            handle: Mutex::new(None),
+        }
+    }
+
+    /// Start polling. Idempotent: a second call while already running is a no-op.
+    pub fn start(&self, app: AppHandle<Wry>) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let running = self.running.clone();
+        let handle = std::thread::spawn(move || watch_loop(app, running));
+        *self.handle.lock().unwrap() = Some(handle);
+    }
+
+    /// Stop polling and wait for the background task to wind down.
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Default for KiCadWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Poll loop: probe every visible socket, emit `kicad://connected` for newly
+/// seen instances and `kicad://disconnected` once a live one fails
+/// [`FAILURE_THRESHOLD`] probes in a row.
+fn watch_loop(app: AppHandle<Wry>, running: Arc<AtomicBool>) {
+    // socket URI -> live instance
+    let mut live: HashMap<String, KiCadInstance> = HashMap::new();
+    // socket URI -> consecutive failed probes (debounce state)
+    let mut failures: HashMap<String, u32> = HashMap::new();
+
+    while running.load(Ordering::SeqCst) {
+        let current: Vec<String> = discover_socket_files()
+            .iter()
+            .map(|p| socket_path_to_uri(p))
+            .collect();
+
+        for uri in &current {
+            match probe_kicad_instance(uri) {
+                Ok(instance) => {
+                    failures.remove(uri);
+                    if !live.contains_key(uri) {
+                        live.insert(uri.clone(), instance.clone());
+                        let _ = app.emit("kicad://connected", &instance);
+                    }
+                }
+                Err(_) => bump_failure(&app, uri, &mut live, &mut failures),
+            }
+        }
+
+        // Sockets that vanished from disk also count as failed probes.
+        let vanished: Vec<String> = live
+            .keys()
+            .filter(|uri| !current.contains(uri))
+            .cloned()
+            .collect();
+        for uri in vanished {
+            bump_failure(&app, &uri, &mut live, &mut failures);
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Record a failed probe for a live instance, disconnecting it once the
+/// failure streak reaches [`FAILURE_THRESHOLD`].
+fn bump_failure(
+    app: &AppHandle<Wry>,
+    uri: &str,
+    live: &mut HashMap<String, KiCadInstance>,
+    failures: &mut HashMap<String, u32>,
+) {
+    if !live.contains_key(uri) {
+        return;
+    }
+    let count = failures.entry(uri.to_string()).or_insert(0);
+    *count += 1;
+    if *count >= FAILURE_THRESHOLD {
+        live.remove(uri);
+        failures.remove(uri);
+        let _ = app.emit("kicad://disconnected", uri);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,176 @@
+// Tool-use subsystem: maps Gemini function calls onto KiCad IPC operations.
+//
+// Each tool pairs a Gemini `functionDeclaration` with a handler that runs the
+// corresponding `kicad` crate operation against a connected instance. Handlers
+// are split into read-only and write operations; writes only execute once the
+// caller has passed the user-confirmation gate.
+
+use std::collections::HashMap;
+
+use kicad::{DocumentType, KiCad};
+use serde_json::{json, Value};
+
+use crate::gemini::FunctionDeclaration;
+
+/// Result of a single tool invocation: a JSON value on success, a message on
+/// failure (fed back to the model as the function response).
+pub type ToolResult = Result<Value, String>;
+
+type Handler = Box<dyn Fn(&KiCad, &Value) -> ToolResult + Send + Sync>;
+
+struct Tool {
+    declaration: FunctionDeclaration,
+    handler: Handler,
+    is_write: bool,
+}
+
+/// Registry mapping Gemini function names to their KiCad handlers.
+pub struct ToolRegistry {
+    tools: HashMap<String, Tool>,
+}
+
+impl ToolRegistry {
+    fn new() -> Self {
+        Self {
+            tools: HashMap::new(),
+        }
+    }
+
+    fn register(
+        &mut self,
+        name: &str,
+        description: &str,
+        parameters: Value,
+        is_write: bool,
+        handler: Handler,
+    ) {
+        let declaration = FunctionDeclaration {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+        };
+        self.tools.insert(
+            name.to_string(),
+            Tool {
+                declaration,
+                handler,
+                is_write,
+            },
+        );
+    }
+
+    /// The `functionDeclarations` to advertise to the model.
+    pub fn declarations(&self) -> Vec<FunctionDeclaration> {
+        self.tools.values().map(|t| t.declaration.clone()).collect()
+    }
+
+    /// Whether `name` is a registered write operation.
+    pub fn is_write(&self, name: &str) -> bool {
+        self.tools.get(name).is_some_and(|t| t.is_write)
+    }
+
+    /// Execute `name` with `args`. Write operations are refused unless
+    /// `allow_writes` is set, so the UI can gate them behind user confirmation.
+    pub fn dispatch(
+        &self,
+        kicad: &KiCad,
+        name: &str,
+        args: &Value,
+        allow_writes: bool,
+    ) -> ToolResult {
+        let tool = self
+            .tools
+            .get(name)
+            .ok_or_else(|| format!("unknown tool: {}", name))?;
+        if tool.is_write && !allow_writes {
+            return Err(format!(
+                "write operation '{}' requires user confirmation",
+                name
+            ));
+        }
+        (tool.handler)(kicad, args)
+    }
+}
+
+/// A small JSON-schema object with no parameters.
+fn no_params() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+// NOTE: the `kicad` IPC crate used here only exposes a read-only surface that
+// we can rely on (`get_version`, `get_open_documents`). Board-mutation
+// primitives such as footprint placement are not part of that surface, so no
+// write tools are declared — we don't advertise a tool to the model that could
+// never succeed. The write-gate machinery (`is_write` / `allow_writes`) is kept
+// ready for a real write operation once the IPC crate exposes one.
+
+/// Build the default registry exposing the KiCad tools.
+pub fn default_registry() -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+
+    registry.register(
+        "list_open_boards",
+        "List the PCB boards currently open in KiCad.",
+        no_params(),
+        false,
+        Box::new(|kicad, _args| {
+            let docs = kicad
+                .get_open_documents(DocumentType::DOCTYPE_PCB)
+                .map_err(|e| e.to_string())?;
+            let boards: Vec<Value> = docs
+                .iter()
+                .map(|doc| {
+                    let path = &doc.project.path;
+                    let name = std::path::Path::new(path)
+                        .file_stem()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or("Unknown Project");
+                    json!({ "project_path": path, "name": name })
+                })
+                .collect();
+            Ok(json!({ "boards": boards }))
+        }),
+    );
+
+    registry.register(
+        "get_kicad_version",
+        "Return the version string of the connected KiCad instance.",
+        no_params(),
+        false,
+        Box::new(|kicad, _args| {
+            let version = kicad.get_version().map_err(|e| e.to_string())?;
+            Ok(json!({ "version": version.to_string() }))
+        }),
+    );
+
+    registry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_write_tools_declared() {
+        let registry = default_registry();
+        // Every advertised tool must be read-only; we never declare a tool the
+        // model could call that cannot succeed.
+        for decl in registry.declarations() {
+            assert!(
+                !registry.is_write(&decl.name),
+                "tool {} is advertised as a write",
+                decl.name
+            );
+        }
+        // Unknown commands are never treated as writes.
+        assert!(!registry.is_write("nope"));
+    }
+
+    #[test]
+    fn test_declarations_cover_read_tools() {
+        let registry = default_registry();
+        let names: Vec<String> = registry.declarations().into_iter().map(|d| d.name).collect();
+        assert!(names.contains(&"list_open_boards".to_string()));
+        assert!(names.contains(&"get_kicad_version".to_string()));
+    }
+}
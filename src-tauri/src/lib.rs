@@ -3,10 +3,16 @@
 mod kicad_ipc;
 mod gemini;
 mod api_key;
+mod chat;
+mod secret;
+mod kicad_tools;
+mod guard;
 
-use kicad_ipc::KiCadInstance;
+use kicad_ipc::{KiCadInstance, KiCadWatcher};
 use api_key::{ApiKeyState, ApiKeyStore};
-use tauri::{Manager, State};
+use chat::ChatState;
+use gemini::ChatTurn;
+use tauri::{Emitter, Manager, State};
 
 #[tauri::command]
 fn echo_message(message: &str) -> String {
@@ -18,6 +24,16 @@ fn detect_kicad_instances() -> Vec<KiCadInstance> {
     kicad_ipc::detect_kicad_instances()
 }
 
+#[tauri::command]
+fn start_watching(app: tauri::AppHandle, watcher: State<KiCadWatcher>) {
+    watcher.start(app);
+}
+
+#[tauri::command]
+fn stop_watching(watcher: State<KiCadWatcher>) {
+    watcher.stop();
+}
+
 #[tauri::command]
 fn check_api_key(state: State<ApiKeyState>) -> bool {
     let store = state.0.lock().unwrap();
@@ -36,6 +52,12 @@ fn set_api_key(state: State<ApiKeyState>, api_key: String) -> Result<(), String>
     store.set_api_key(api_key)
 }
 
+#[tauri::command]
+fn secret_backend(state: State<ApiKeyState>) -> secret::SecretBackend {
+    let store = state.0.lock().unwrap();
+    store.secret_backend()
+}
+
 #[tauri::command]
 async fn send_message(
     state: State<'_, ApiKeyState>,
@@ -49,7 +71,8 @@ async fn send_message(
     
     match api_key {
         Some(key) => {
-            gemini::send_message_to_gemini(&key, &model, &message)
+            let history = vec![ChatTurn::user(message)];
+            gemini::send_message_to_gemini(&key, &model, &history)
                 .await
                 .map_err(|e| format!("Gemini API error: {}", e))
         }
@@ -57,27 +80,175 @@ async fn send_message(
     }
 }
 
+#[tauri::command]
+fn new_session(chat: State<ChatState>, session_id: String) {
+    let mut sessions = chat.sessions.lock().unwrap();
+    sessions.entry(session_id).or_default();
+}
+
+#[tauri::command]
+fn clear_session(chat: State<ChatState>, session_id: String) {
+    let mut sessions = chat.sessions.lock().unwrap();
+    sessions.remove(&session_id);
+}
+
+/// Append a user turn to the session, send the full history to Gemini, store
+/// the model's reply, and return it. History is trimmed to the session's turn
+/// and token budget so long sessions stay within the context window.
+#[tauri::command]
+async fn append_and_send(
+    api_state: State<'_, ApiKeyState>,
+    chat: State<'_, ChatState>,
+    session_id: String,
+    message: String,
+    model: String,
+) -> Result<String, String> {
+    let api_key = {
+        let store = api_state.0.lock().unwrap();
+        store.get_api_key()
+    };
+    let key = api_key.ok_or_else(|| "API key not configured".to_string())?;
+
+    let budget = chat.budget;
+
+    // Build the in-flight history locally (stored history + this user turn) so
+    // a failed call leaves the stored session untouched. Roles must alternate,
+    // so a dangling user turn from an earlier failure would corrupt every
+    // subsequent request.
+    let user_turn = ChatTurn::user(message);
+    let history = {
+        let sessions = chat.sessions.lock().unwrap();
+        let mut history = sessions.get(&session_id).cloned().unwrap_or_default();
+        history.push(user_turn.clone());
+        chat::trim_history(&mut history, &budget);
+        history
+    };
+
+    let reply = gemini::send_message_to_gemini(&key, &model, &history)
+        .await
+        .map_err(|e| format!("Gemini API error: {}", e))?;
+
+    // Only now that the call succeeded, commit the user turn and the reply.
+    {
+        let mut sessions = chat.sessions.lock().unwrap();
+        let turns = sessions.entry(session_id).or_default();
+        turns.push(user_turn);
+        turns.push(ChatTurn::model(reply.clone()));
+        chat::trim_history(turns, &budget);
+    }
+
+    Ok(reply)
+}
+
+#[tauri::command]
+async fn send_message_stream(
+    app: tauri::AppHandle,
+    state: State<'_, ApiKeyState>,
+    stream_id: String,
+    message: String,
+    model: String,
+) -> Result<(), String> {
+    let api_key = {
+        let store = state.0.lock().unwrap();
+        store.get_api_key()
+    };
+
+    let key = match api_key {
+        Some(key) => key,
+        None => return Err("API key not configured".to_string()),
+    };
+
+    // Drive the SSE stream on a background task so the command returns
+    // immediately and chunks arrive on the event channel as they are parsed.
+    tauri::async_runtime::spawn(async move {
+        match gemini::stream_message_to_gemini(&app, &key, &model, &message, &stream_id).await {
+            Ok(()) => {
+                let _ = app.emit(&format!("gemini://done/{}", stream_id), ());
+            }
+            Err(e) => {
+                let _ = app.emit(
+                    &format!("gemini://error/{}", stream_id),
+                    format!("Gemini API error: {}", e),
+                );
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Send a message to Gemini with the KiCad tool registry attached, letting the
+/// model query the board at `socket_path` via function calls before returning
+/// its final text. The default registry declares only read-only tools, so
+/// `allow_writes` does not enable any board modification.
+#[tauri::command]
+async fn send_message_with_tools(
+    state: State<'_, ApiKeyState>,
+    message: String,
+    model: String,
+    socket_path: String,
+    allow_writes: bool,
+) -> Result<String, String> {
+    let api_key = {
+        let store = state.0.lock().unwrap();
+        store.get_api_key()
+    };
+    let key = api_key.ok_or_else(|| "API key not configured".to_string())?;
+
+    let registry = kicad_tools::default_registry();
+    let history = vec![ChatTurn::user(message)];
+    gemini::send_message_with_tools(&key, &model, &history, &registry, &socket_path, allow_writes)
+        .await
+        .map_err(|e| format!("Gemini API error: {}", e))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .manage(ApiKeyState(std::sync::Mutex::new(ApiKeyStore::new())))
+        .manage(ChatState::new())
+        .manage(KiCadWatcher::new())
         .setup(|app| {
             let handle = app.handle().clone();
-            let state = app.state::<ApiKeyState>();
-            let mut store = state.0.lock().unwrap();
-            store.set_app_handle(handle);
+            {
+                let state = app.state::<ApiKeyState>();
+                let mut store = state.0.lock().unwrap();
+                store.set_app_handle(handle.clone());
+            }
+            // Begin watching for KiCad instances as soon as the app is up.
+            app.state::<KiCadWatcher>().start(handle);
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
-            echo_message,
-            detect_kicad_instances,
-            check_api_key,
-            get_api_key,
-            set_api_key,
-            send_message
-        ])
+        .invoke_handler({
+            // Guard the generated handlers: reject calls from webviews other
+            // than the main one and secret-bearing commands from untrusted origins.
+            let policy = guard::TrustedOrigins::default();
+            let handler = tauri::generate_handler![
+                echo_message,
+                detect_kicad_instances,
+                start_watching,
+                stop_watching,
+                check_api_key,
+                get_api_key,
+                set_api_key,
+                secret_backend,
+                send_message,
+                send_message_stream,
+                new_session,
+                append_and_send,
+                clear_session,
+                send_message_with_tools
+            ];
+            move |invoke| match guard::check_invoke(&policy, &invoke) {
+                Ok(()) => handler(invoke),
+                Err(reason) => {
+                    invoke.resolver.reject(reason);
+                    true
+                }
+            }
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,201 @@
+// Secure storage backend for the Gemini API key.
+//
+// The key is kept out of the plaintext JSON config. We prefer the OS secret
+// vault (macOS Keychain / Windows Credential Manager / libsecret via the
+// `keyring` crate); when no platform keychain is available we fall back to an
+// AES-256-GCM envelope encrypted with an app-generated key held in a
+// restricted-permission file alongside the app config.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, OsRng};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use keyring::Entry;
+use serde::Serialize;
+
+const KEYRING_SERVICE: &str = "NewKiAssist";
+const KEYRING_USER: &str = "gemini_api_key";
+
+const KEY_MATERIAL_FILE: &str = "kiassist_secret.key";
+const CIPHERTEXT_FILE: &str = "kiassist_secret.enc";
+
+const NONCE_LEN: usize = 12;
+
+/// Which backend is protecting the stored key.
+///
+/// Surfaced to the UI so the user can tell whether their key is
+/// hardware/OS-protected or merely file-encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecretBackend {
+    /// Hardware/OS-protected vault (Keychain / Credential Manager / libsecret).
+    Keychain,
+    /// AES-256-GCM envelope keyed by a restricted-permission local file.
+    FileEncrypted,
+}
+
+fn keyring_entry() -> keyring::Result<Entry> {
+    Entry::new(KEYRING_SERVICE, KEYRING_USER)
+}
+
+/// Store `key`, preferring the OS keychain and falling back to file encryption.
+pub fn store_secret(dir: &Path, key: &str) -> Result<SecretBackend, String> {
+    if let Ok(entry) = keyring_entry() {
+        if entry.set_password(key).is_ok() {
+            return Ok(SecretBackend::Keychain);
+        }
+    }
+    store_file_encrypted(dir, key)?;
+    Ok(SecretBackend::FileEncrypted)
+}
+
+/// Load the stored key and the backend it came from, if one is present.
+pub fn load_secret(dir: &Path) -> Option<(String, SecretBackend)> {
+    if let Ok(entry) = keyring_entry() {
+        if let Ok(password) = entry.get_password() {
+            return Some((password, SecretBackend::Keychain));
+        }
+    }
+    load_file_encrypted(dir).map(|key| (key, SecretBackend::FileEncrypted))
+}
+
+/// The backend a newly stored key would use, without writing anything.
+pub fn active_backend() -> SecretBackend {
+    match keyring_entry().and_then(|entry| entry.get_password()) {
+        // `NoEntry` means the keychain is reachable but holds no key yet.
+        Ok(_) | Err(keyring::Error::NoEntry) => SecretBackend::Keychain,
+        Err(_) => SecretBackend::FileEncrypted,
+    }
+}
+
+fn key_material_path(dir: &Path) -> PathBuf {
+    dir.join(KEY_MATERIAL_FILE)
+}
+
+fn ciphertext_path(dir: &Path) -> PathBuf {
+    dir.join(CIPHERTEXT_FILE)
+}
+
+/// Load the symmetric key material, generating and persisting it on first use.
+fn load_or_create_key(dir: &Path) -> Result<Key<Aes256Gcm>, String> {
+    let path = key_material_path(dir);
+    if let Ok(bytes) = fs::read(&path) {
+        if bytes.len() == 32 {
+            return Ok(*Key::<Aes256Gcm>::from_slice(&bytes));
+        }
+    }
+
+    let key = Aes256Gcm::generate_key(&mut OsRng);
+    write_restricted(&path, key.as_slice())?;
+    Ok(key)
+}
+
+fn store_file_encrypted(dir: &Path, secret: &str) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create secret dir: {}", e))?;
+
+    let key = load_or_create_key(dir)?;
+    let cipher = Aes256Gcm::new(&key);
+
+    let mut nonce_bytes = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+
+    let ciphertext = cipher
+        .encrypt(nonce, secret.as_bytes())
+        .map_err(|e| format!("Failed to encrypt API key: {}", e))?;
+
+    // Envelope is nonce || ciphertext.
+    let mut envelope = nonce_bytes.to_vec();
+    envelope.extend_from_slice(&ciphertext);
+    write_restricted(&ciphertext_path(dir), &envelope)
+}
+
+fn load_file_encrypted(dir: &Path) -> Option<String> {
+    let envelope = fs::read(ciphertext_path(dir)).ok()?;
+    if envelope.len() <= NONCE_LEN {
+        return None;
+    }
+    let (nonce_bytes, ciphertext) = envelope.split_at(NONCE_LEN);
+
+    let key = load_or_create_key(dir).ok()?;
+    let cipher = Aes256Gcm::new(&key);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce_bytes), ciphertext)
+        .ok()?;
+    String::from_utf8(plaintext).ok()
+}
+
+/// Write `bytes` to `path` with owner-only permissions where supported.
+fn write_restricted(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::write(path, bytes).map_err(|e| format!("Failed to write secret file: {}", e))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict secret file permissions: {}", e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unique per-test scratch directory, cleaned up on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("kiassist-secret-test-{}", name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_file_encrypt_roundtrip() {
+        let tmp = TempDir::new("roundtrip");
+        store_file_encrypted(&tmp.0, "super-secret-key").unwrap();
+
+        // Ciphertext on disk must not contain the plaintext.
+        let envelope = fs::read(ciphertext_path(&tmp.0)).unwrap();
+        assert!(!envelope
+            .windows("super-secret-key".len())
+            .any(|w| w == b"super-secret-key"));
+
+        assert_eq!(
+            load_file_encrypted(&tmp.0).as_deref(),
+            Some("super-secret-key")
+        );
+    }
+
+    #[test]
+    fn test_file_decrypt_missing_returns_none() {
+        let tmp = TempDir::new("missing");
+        assert_eq!(load_file_encrypted(&tmp.0), None);
+    }
+
+    #[test]
+    fn test_file_decrypt_tampered_returns_none() {
+        let tmp = TempDir::new("tampered");
+        store_file_encrypted(&tmp.0, "another-key").unwrap();
+
+        let mut envelope = fs::read(ciphertext_path(&tmp.0)).unwrap();
+        *envelope.last_mut().unwrap() ^= 0xff;
+        fs::write(ciphertext_path(&tmp.0), &envelope).unwrap();
+
+        // GCM authentication must reject the tampered ciphertext.
+        assert_eq!(load_file_encrypted(&tmp.0), None);
+    }
+}